@@ -0,0 +1,138 @@
+//! `std` is on by default and pulls in OS randomness for the convenience
+//! entry points (`encrypt`, `seal_to`, `StreamEncryptor::new`). Disabling it
+//! drops that OS-RNG dependency so the crate links on targets without one
+//! (e.g. wasm32-unknown-unknown without a wired-up `getrandom` backend);
+//! use the `_with_rng` counterpart of each entry point and supply your own
+//! [`RngCore`] instead.
+//!
+//! This only gets you `--no-default-features`, not `#![no_std]`: `thiserror`
+//! unconditionally implements `std::error::Error` for [`Error`], and the
+//! other dependencies here (`base64`, `hkdf`, `chacha20`, `hmac`) are
+//! otherwise core/alloc-friendly. A true `no_std` build would need an
+//! `Error` type that doesn't go through `thiserror`.
+//!
+//! CI checks `cargo check --target wasm32-unknown-unknown --no-default-features`
+//! on every push, since the test vectors (which need `std`) can't catch a
+//! regression here on their own.
+
+use base64::Engine;
+use hkdf::Hkdf;
+use secp256k1::ecdh::shared_secret_point;
+use secp256k1::{Parity, PublicKey, SecretKey, XOnlyPublicKey};
+use sha2::Sha256;
+
+pub use rand_core::RngCore;
+
+mod error;
+pub use error::Error;
+
+mod types;
+pub use types::{ConversationKey, Nonce};
+
+mod version;
+pub use version::Version;
+
+mod v2;
+#[cfg(test)]
+pub(crate) use v2::{calc_padding, encrypt_inner};
+
+mod stream;
+pub use stream::{StreamDecryptor, StreamEncryptor, SEGMENT_LEN};
+
+mod seal;
+#[cfg(feature = "std")]
+pub use seal::seal_to;
+pub use seal::{open, seal_to_with_rng};
+
+#[cfg(test)]
+mod tests;
+
+/// A conversation key is the long-term secret that two nostr identities share.
+fn get_shared_point(private_key_a: SecretKey, x_only_public_key_b: XOnlyPublicKey) -> [u8; 32] {
+    let pubkey = PublicKey::from_x_only_public_key(x_only_public_key_b, Parity::Even);
+    let mut ssp = shared_secret_point(&pubkey, &private_key_a)
+        .as_slice()
+        .to_owned();
+    ssp.resize(32, 0); // toss the Y part
+    ssp.try_into().unwrap()
+}
+
+pub fn get_conversation_key(
+    private_key_a: SecretKey,
+    x_only_public_key_b: XOnlyPublicKey,
+) -> ConversationKey {
+    let shared_point = get_shared_point(private_key_a, x_only_public_key_b);
+    let (convo_key, _hkdf) =
+        Hkdf::<Sha256>::extract(Some("nip44-v2".as_bytes()), shared_point.as_slice());
+    ConversationKey::new(convo_key.into())
+}
+
+/// Encrypt a plaintext message with a conversation key, under the given
+/// payload format version. The output is a base64 encoded string that can be
+/// placed into message contents.
+#[cfg(feature = "std")]
+pub fn encrypt(
+    version: Version,
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    match version {
+        Version::V2 => v2::encrypt_inner(conversation_key, plaintext, None),
+        Version::V2Deterministic => Err(Error::NoRandomizedForm),
+    }
+}
+
+/// Same as [`encrypt`], but draws the nonce from `rng` instead of the OS
+/// default. Available without the `std` feature.
+pub fn encrypt_with_rng<R: RngCore>(
+    rng: &mut R,
+    version: Version,
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    match version {
+        Version::V2 => v2::encrypt_inner_with_rng(rng, conversation_key, plaintext, None),
+        Version::V2Deterministic => Err(Error::NoRandomizedForm),
+    }
+}
+
+/// Encrypt `plaintext` deterministically: the nonce is derived as an HMAC
+/// of the plaintext instead of drawn from randomness, so repeating
+/// `(conversation_key, plaintext)` reuses the same nonce rather than
+/// risking a random collision, at the cost of leaking message equality to
+/// anyone comparing ciphertexts. Prefer [`encrypt`] unless that tradeoff is
+/// wanted; [`decrypt`] handles both transparently.
+///
+/// The [`Version::V2Deterministic`] byte this produces is a `gnip44`-only
+/// extension, not part of the NIP-44 spec -- only `gnip44`'s own [`decrypt`]
+/// understands it. A spec-compliant NIP-44 client on the other end of a
+/// conversation will reject these payloads as an unknown version.
+pub fn encrypt_deterministic(
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    v2::encrypt_deterministic(conversation_key, plaintext)
+}
+
+/// Decrypt the base64 encrypted contents with a conversation key.
+///
+/// The leading byte of the decoded payload identifies its [`Version`]; this
+/// dispatches to the codec for that version. [`Version::V2`] and
+/// [`Version::V2Deterministic`] share the same wire layout and codec, since
+/// they only differ in how the encrypting side chose the nonce.
+pub fn decrypt(
+    conversation_key: &ConversationKey,
+    base64_ciphertext: &str,
+) -> Result<String, Error> {
+    if base64_ciphertext.as_bytes()[0] == b'#' {
+        return Err(Error::UnsupportedFutureVersion);
+    }
+    let binary_ciphertext: Vec<u8> =
+        base64::engine::general_purpose::STANDARD.decode(base64_ciphertext)?;
+    let version = Version::from_byte(binary_ciphertext[0])?;
+    match version {
+        Version::V2 | Version::V2Deterministic => {
+            v2::decrypt_inner(conversation_key, &binary_ciphertext)
+        }
+    }
+}
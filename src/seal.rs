@@ -0,0 +1,127 @@
+//! Sealed multi-recipient envelopes.
+//!
+//! NIP-44's conversation key is strictly pairwise, so a group message would
+//! otherwise need to be encrypted once per recipient. Here the plaintext is
+//! encrypted once under a fresh random content key, and that content key is
+//! then wrapped separately for each recipient (tagged with their x-only
+//! pubkey) using the ordinary pairwise conversation key, so any one
+//! recipient's secret key unwraps the same content key and recovers the
+//! same plaintext.
+
+use base64::Engine;
+use rand_core::RngCore;
+use secp256k1::{Secp256k1, SecretKey, XOnlyPublicKey};
+
+use crate::{get_conversation_key, v2, ConversationKey, Error};
+
+const SEAL_VERSION: u8 = 1;
+const PUBKEY_LEN: usize = 32;
+
+/// Encrypt `plaintext` once under a fresh content key, then wrap that
+/// content key separately for each of `recipients` so any of their secret
+/// keys can recover it. Draws the content key from the OS RNG.
+#[cfg(feature = "std")]
+pub fn seal_to(
+    recipients: &[XOnlyPublicKey],
+    sender_sec: SecretKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    seal_to_with_rng(&mut rand_core::OsRng, recipients, sender_sec, plaintext)
+}
+
+/// Same as [`seal_to`], but draws the content key from `rng` instead of the
+/// OS default. Available without the `std` feature.
+pub fn seal_to_with_rng<R: RngCore>(
+    rng: &mut R,
+    recipients: &[XOnlyPublicKey],
+    sender_sec: SecretKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    let secp = Secp256k1::new();
+
+    let mut content_key_bytes = [0u8; 32];
+    rng.fill_bytes(&mut content_key_bytes);
+    let content_key = ConversationKey::new(content_key_bytes);
+
+    let sender_pub = sender_sec.x_only_public_key(&secp).0;
+
+    let mut envelope = vec![SEAL_VERSION];
+    envelope.extend_from_slice(&sender_pub.serialize());
+    envelope.extend_from_slice(&(recipients.len() as u16).to_be_bytes());
+
+    for recipient_pub in recipients {
+        let recipient_key = get_conversation_key(sender_sec, *recipient_pub);
+        let wrapped = v2::encrypt_inner_with_rng(
+            rng,
+            &recipient_key,
+            &hex::encode(content_key_bytes),
+            None,
+        )?;
+        let wrapped_bytes = base64::engine::general_purpose::STANDARD.decode(wrapped)?;
+
+        envelope.extend_from_slice(&recipient_pub.serialize());
+        envelope.extend_from_slice(&(wrapped_bytes.len() as u16).to_be_bytes());
+        envelope.extend_from_slice(&wrapped_bytes);
+    }
+
+    let body = v2::encrypt_inner_with_rng(rng, &content_key, plaintext, None)?;
+    let body_bytes = base64::engine::general_purpose::STANDARD.decode(body)?;
+    envelope.extend_from_slice(&body_bytes);
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(&envelope))
+}
+
+/// Reads `len` bytes starting at `pos`, failing with [`Error::InvalidEnvelope`]
+/// instead of panicking if the envelope doesn't have that many bytes left.
+fn take(envelope: &[u8], pos: usize, len: usize) -> Result<&[u8], Error> {
+    envelope.get(pos..pos + len).ok_or(Error::InvalidEnvelope)
+}
+
+/// Open an envelope produced by [`seal_to`] with one recipient's secret key.
+/// Fails with [`Error::InvalidEnvelope`] if `envelope` is malformed or
+/// truncated (it comes from an untrusted sender, so this must never panic),
+/// or [`Error::InvalidMac`] if `sec` isn't one of the recipients.
+pub fn open(sec: SecretKey, envelope: &str) -> Result<String, Error> {
+    let secp = Secp256k1::new();
+    let envelope = base64::engine::general_purpose::STANDARD.decode(envelope)?;
+
+    if envelope.first().copied() != Some(SEAL_VERSION) {
+        return Err(Error::UnknownVersion);
+    }
+    let mut pos = 1;
+
+    let sender_pub = XOnlyPublicKey::from_slice(take(&envelope, pos, PUBKEY_LEN)?)
+        .map_err(|_| Error::InvalidEnvelope)?;
+    pos += PUBKEY_LEN;
+
+    let recipient_count =
+        u16::from_be_bytes(take(&envelope, pos, 2)?.try_into().unwrap()) as usize;
+    pos += 2;
+
+    let my_pub = sec.x_only_public_key(&secp).0.serialize();
+    let mut wrapped_key: Option<Vec<u8>> = None;
+
+    for _ in 0..recipient_count {
+        let recipient_pub: [u8; PUBKEY_LEN] = take(&envelope, pos, PUBKEY_LEN)?.try_into().unwrap();
+        pos += PUBKEY_LEN;
+        let len = u16::from_be_bytes(take(&envelope, pos, 2)?.try_into().unwrap()) as usize;
+        pos += 2;
+        let slot = take(&envelope, pos, len)?;
+        pos += len;
+
+        if recipient_pub == my_pub {
+            wrapped_key = Some(slot.to_owned());
+        }
+    }
+
+    let wrapped_key = wrapped_key.ok_or(Error::InvalidMac)?;
+    let recipient_key = get_conversation_key(sec, sender_pub);
+    let content_key_hex = v2::decrypt_inner(&recipient_key, &wrapped_key)?;
+    let content_key_bytes: [u8; 32] = hex::decode(content_key_hex)
+        .map_err(|_| Error::InvalidMac)?
+        .try_into()
+        .map_err(|_| Error::InvalidMac)?;
+    let content_key = ConversationKey::new(content_key_bytes);
+
+    v2::decrypt_inner(&content_key, &envelope[pos..])
+}
@@ -0,0 +1,256 @@
+//! Streaming encryption for plaintexts too large to buffer and pad as a
+//! single NIP-44 message, using the Rogaway-Hoang STREAM online-AEAD
+//! construction: the plaintext is split into fixed-size segments, each
+//! sealed under a nonce built from a random prefix, a big-endian segment
+//! counter, and a flag byte that is 1 only on the last segment. The flag
+//! stops truncation attacks; the counter (derived from segment position,
+//! never carried on the wire) stops reordering.
+
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sha2::Sha256;
+
+use crate::{ConversationKey, Error};
+
+const STREAM_VERSION: u8 = 1;
+const PREFIX_LEN: usize = 7;
+const COUNTER_LEN: usize = 4;
+const HEADER_LEN: usize = 1 + PREFIX_LEN;
+const MAC_LEN: usize = 32;
+
+/// Plaintext bytes sealed per segment. Kept fixed so the per-segment nonce
+/// can be derived purely from the segment's position in the stream.
+pub const SEGMENT_LEN: usize = 64 * 1024;
+
+fn stream_keys(
+    conversation_key: &ConversationKey,
+    prefix: &[u8; PREFIX_LEN],
+) -> Result<([u8; 32], [u8; 32]), Error> {
+    let hk: Hkdf<Sha256> = Hkdf::from_prk(conversation_key.as_bytes())
+        .map_err(|_| Error::HkdfLength(conversation_key.as_bytes().len()))?;
+    let mut info = Vec::with_capacity(b"nip44-v2-stream".len() + PREFIX_LEN);
+    info.extend_from_slice(b"nip44-v2-stream");
+    info.extend_from_slice(prefix);
+    let mut okm = [0u8; 64];
+    hk.expand(&info, &mut okm)
+        .map_err(|_| Error::HkdfLength(okm.len()))?;
+    let chacha_key: [u8; 32] = okm[0..32].try_into().unwrap();
+    let hmac_key: [u8; 32] = okm[32..64].try_into().unwrap();
+    Ok((chacha_key, hmac_key))
+}
+
+fn segment_nonce(prefix: &[u8; PREFIX_LEN], counter: u32, is_final: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[0..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..PREFIX_LEN + COUNTER_LEN].copy_from_slice(&counter.to_be_bytes());
+    nonce[PREFIX_LEN + COUNTER_LEN] = is_final as u8;
+    nonce
+}
+
+fn seal_segment(
+    chacha_key: &[u8; 32],
+    hmac_key: &[u8; 32],
+    prefix: &[u8; PREFIX_LEN],
+    counter: u32,
+    is_final: bool,
+    plaintext: &[u8],
+) -> Vec<u8> {
+    let nonce = segment_nonce(prefix, counter, is_final);
+    let mut buffer = plaintext.to_owned();
+    let mut cipher = ChaCha20::new(&(*chacha_key).into(), &nonce.into());
+    cipher.apply_keystream(&mut buffer);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(hmac_key).expect("hmac key is always 32 bytes");
+    mac.update(&nonce);
+    mac.update(&buffer);
+    buffer.extend_from_slice(&mac.finalize().into_bytes());
+    buffer
+}
+
+fn open_segment(
+    chacha_key: &[u8; 32],
+    hmac_key: &[u8; 32],
+    prefix: &[u8; PREFIX_LEN],
+    counter: u32,
+    is_final: bool,
+    sealed: &[u8],
+) -> Result<Vec<u8>, Error> {
+    if sealed.len() < MAC_LEN {
+        return Err(Error::StreamTruncated);
+    }
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - MAC_LEN);
+    let nonce = segment_nonce(prefix, counter, is_final);
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(hmac_key).expect("hmac key is always 32 bytes");
+    mac.update(&nonce);
+    mac.update(ciphertext);
+    let calculated = mac.finalize().into_bytes();
+    if !constant_time_eq::constant_time_eq(tag, calculated.as_slice()) {
+        return Err(Error::InvalidMac);
+    }
+    let mut buffer = ciphertext.to_owned();
+    let mut cipher = ChaCha20::new(&(*chacha_key).into(), &nonce.into());
+    cipher.apply_keystream(&mut buffer);
+    Ok(buffer)
+}
+
+/// Encrypts a plaintext of arbitrary length incrementally, in fixed-size
+/// segments, instead of buffering and padding it all at once.
+pub struct StreamEncryptor {
+    chacha_key: [u8; 32],
+    hmac_key: [u8; 32],
+    prefix: [u8; PREFIX_LEN],
+    counter: u32,
+    buffer: Vec<u8>,
+    header_sent: bool,
+}
+
+impl StreamEncryptor {
+    /// Start a new stream under the given conversation key, picking a fresh
+    /// random nonce prefix from the OS RNG.
+    #[cfg(feature = "std")]
+    pub fn new(conversation_key: &ConversationKey) -> Result<StreamEncryptor, Error> {
+        Self::new_with_rng(&mut rand_core::OsRng, conversation_key)
+    }
+
+    /// Same as [`Self::new`], but draws the nonce prefix from `rng` instead
+    /// of the OS default. Available without the `std` feature.
+    pub fn new_with_rng<R: RngCore>(
+        rng: &mut R,
+        conversation_key: &ConversationKey,
+    ) -> Result<StreamEncryptor, Error> {
+        let mut prefix = [0u8; PREFIX_LEN];
+        rng.fill_bytes(&mut prefix);
+        let (chacha_key, hmac_key) = stream_keys(conversation_key, &prefix)?;
+        Ok(StreamEncryptor {
+            chacha_key,
+            hmac_key,
+            prefix,
+            counter: 0,
+            buffer: Vec::new(),
+            header_sent: false,
+        })
+    }
+
+    /// Feed more plaintext into the stream. Returns the header (on the
+    /// first call) followed by any segments that are now fully sealed;
+    /// bytes too few to fill a segment are held until the next call or
+    /// [`Self::finalize`].
+    pub fn update(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !self.header_sent {
+            out.push(STREAM_VERSION);
+            out.extend_from_slice(&self.prefix);
+            self.header_sent = true;
+        }
+        self.buffer.extend_from_slice(chunk);
+        while self.buffer.len() >= SEGMENT_LEN {
+            let segment: Vec<u8> = self.buffer.drain(..SEGMENT_LEN).collect();
+            out.extend(seal_segment(
+                &self.chacha_key,
+                &self.hmac_key,
+                &self.prefix,
+                self.counter,
+                false,
+                &segment,
+            ));
+            self.counter += 1;
+        }
+        out
+    }
+
+    /// Seal whatever plaintext remains as the final segment (which may be
+    /// empty) and consume the stream.
+    pub fn finalize(self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !self.header_sent {
+            out.push(STREAM_VERSION);
+            out.extend_from_slice(&self.prefix);
+        }
+        out.extend(seal_segment(
+            &self.chacha_key,
+            &self.hmac_key,
+            &self.prefix,
+            self.counter,
+            true,
+            &self.buffer,
+        ));
+        out
+    }
+}
+
+/// Decrypts a stream produced by [`StreamEncryptor`] incrementally.
+pub struct StreamDecryptor {
+    conversation_key: ConversationKey,
+    keys: Option<([u8; 32], [u8; 32], [u8; PREFIX_LEN])>,
+    counter: u32,
+    buffer: Vec<u8>,
+}
+
+impl StreamDecryptor {
+    pub fn new(conversation_key: ConversationKey) -> StreamDecryptor {
+        StreamDecryptor {
+            conversation_key,
+            keys: None,
+            counter: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Feed more ciphertext bytes. Returns plaintext for every segment that
+    /// can be recognized as non-final, i.e. one that is followed by at
+    /// least one more byte of ciphertext; the last segment is only ever
+    /// decrypted by [`Self::finalize`], since only then is it known to be
+    /// the last one.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>, Error> {
+        self.buffer.extend_from_slice(chunk);
+        if self.keys.is_none() {
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(Vec::new());
+            }
+            let header: Vec<u8> = self.buffer.drain(..HEADER_LEN).collect();
+            if header[0] != STREAM_VERSION {
+                return Err(Error::UnknownVersion);
+            }
+            let prefix: [u8; PREFIX_LEN] = header[1..].try_into().unwrap();
+            let (chacha_key, hmac_key) = stream_keys(&self.conversation_key, &prefix)?;
+            self.keys = Some((chacha_key, hmac_key, prefix));
+        }
+        let (chacha_key, hmac_key, prefix) = self.keys.as_ref().unwrap();
+
+        let sealed_len = SEGMENT_LEN + MAC_LEN;
+        let mut out = Vec::new();
+        while self.buffer.len() > sealed_len {
+            let sealed: Vec<u8> = self.buffer.drain(..sealed_len).collect();
+            out.extend(open_segment(
+                chacha_key,
+                hmac_key,
+                prefix,
+                self.counter,
+                false,
+                &sealed,
+            )?);
+            self.counter += 1;
+        }
+        Ok(out)
+    }
+
+    /// Decrypt the final segment and consume the stream. Fails with
+    /// [`Error::InvalidMac`] if the stream was truncated or reordered, since
+    /// the held-back bytes won't authenticate under the final-segment nonce
+    /// unless they really were sealed that way.
+    pub fn finalize(self) -> Result<Vec<u8>, Error> {
+        let (chacha_key, hmac_key, prefix) = self.keys.ok_or(Error::StreamTruncated)?;
+        open_segment(
+            &chacha_key,
+            &hmac_key,
+            &prefix,
+            self.counter,
+            true,
+            &self.buffer,
+        )
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// The long-term secret shared between two nostr identities, as computed by
+/// [`crate::get_conversation_key`]. The bytes are scrubbed from memory on drop
+/// so they don't linger in a freed allocation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ConversationKey(Zeroizing<[u8; 32]>);
+
+impl ConversationKey {
+    /// Wrap raw conversation-key bytes (e.g. from a test vector).
+    pub fn new(bytes: [u8; 32]) -> ConversationKey {
+        ConversationKey(Zeroizing::new(bytes))
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Hex-encode the raw key. An explicit opt-in, unlike [`Debug`](fmt::Debug),
+    /// since anything printed this way should be treated as compromised.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_slice())
+    }
+}
+
+impl fmt::Debug for ConversationKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ConversationKey(..)")
+    }
+}
+
+/// The 32-byte nonce used to derive a single message's encryption, chacha20
+/// IV, and HMAC keys from a [`ConversationKey`]. Scrubbed from memory on drop.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Nonce(Zeroizing<[u8; 32]>);
+
+impl Nonce {
+    /// Wrap raw nonce bytes (e.g. from a test vector).
+    pub fn new(bytes: [u8; 32]) -> Nonce {
+        Nonce(Zeroizing::new(bytes))
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Hex-encode the raw nonce. An explicit opt-in, unlike
+    /// [`Debug`](fmt::Debug), since anything printed this way should be
+    /// treated as compromised.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_slice())
+    }
+}
+
+impl fmt::Debug for Nonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Nonce(..)")
+    }
+}
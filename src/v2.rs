@@ -0,0 +1,210 @@
+//! Codec for [`crate::Version::V2`], the only NIP-44 payload format defined so far.
+
+use base64::Engine;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sha2::Sha256;
+
+use crate::{ConversationKey, Error, Nonce, Version};
+
+struct MessageKeys([u8; 76]);
+
+impl MessageKeys {
+    #[inline]
+    pub fn zero() -> MessageKeys {
+        MessageKeys([0; 76])
+    }
+
+    #[inline]
+    pub fn encryption(&self) -> [u8; 32] {
+        self.0[0..32].try_into().unwrap()
+    }
+
+    #[inline]
+    pub fn nonce(&self) -> [u8; 12] {
+        self.0[32..44].try_into().unwrap()
+    }
+
+    #[inline]
+    pub fn auth(&self) -> [u8; 32] {
+        self.0[44..76].try_into().unwrap()
+    }
+}
+
+fn get_message_keys(
+    conversation_key: &ConversationKey,
+    nonce: &Nonce,
+) -> Result<MessageKeys, Error> {
+    let hk: Hkdf<Sha256> = match Hkdf::from_prk(conversation_key.as_bytes()) {
+        Ok(hk) => hk,
+        Err(_) => return Err(Error::HkdfLength(conversation_key.as_bytes().len())),
+    };
+    let mut message_keys: MessageKeys = MessageKeys::zero();
+    if hk.expand(&nonce.as_bytes()[..], &mut message_keys.0).is_err() {
+        return Err(Error::HkdfLength(message_keys.0.len()));
+    }
+    Ok(message_keys)
+}
+
+pub(crate) fn calc_padding(len: usize) -> usize {
+    if len < 32 {
+        return 32;
+    }
+    let nextpower = 1 << ((len - 1).ilog2() + 1);
+    let chunk = if nextpower <= 256 { 32 } else { nextpower / 8 };
+    if len <= 32 {
+        32
+    } else {
+        chunk * (((len - 1) / chunk) + 1)
+    }
+}
+
+fn pad(unpadded: &str) -> Result<Vec<u8>, Error> {
+    let len: usize = unpadded.len();
+    if len < 1 {
+        return Err(Error::MessageIsEmpty);
+    }
+    if len > 65536 - 128 {
+        return Err(Error::MessageIsTooLong);
+    }
+
+    let mut padded: Vec<u8> = Vec::new();
+    padded.extend_from_slice(&(len as u16).to_be_bytes());
+    padded.extend_from_slice(unpadded.as_bytes());
+    padded.extend(std::iter::repeat_n(0, calc_padding(len) - len));
+    Ok(padded)
+}
+
+fn seal(
+    version: Version,
+    conversation_key: &ConversationKey,
+    nonce: &Nonce,
+    plaintext: &str,
+) -> Result<String, Error> {
+    let keys = get_message_keys(conversation_key, nonce)?;
+    let mut buffer = pad(plaintext)?;
+    let mut cipher = ChaCha20::new(&keys.encryption().into(), &keys.nonce().into());
+    cipher.apply_keystream(&mut buffer);
+    let mut mac = Hmac::<Sha256>::new_from_slice(&keys.auth())?;
+    mac.update(nonce.as_bytes());
+    mac.update(&buffer);
+    let mac_bytes = mac.finalize().into_bytes();
+
+    let mut pre_base64: Vec<u8> = vec![version.byte()];
+    pre_base64.extend_from_slice(nonce.as_bytes());
+    pre_base64.extend_from_slice(&buffer);
+    pre_base64.extend_from_slice(&mac_bytes);
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(&pre_base64))
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn encrypt_inner(
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+    override_random_nonce: Option<&Nonce>,
+) -> Result<String, Error> {
+    encrypt_inner_with_rng(&mut rand_core::OsRng, conversation_key, plaintext, override_random_nonce)
+}
+
+/// Same as the `std`-only [`encrypt_inner`], but draws the nonce from
+/// `rng` instead of the OS default, so callers without OS randomness
+/// (e.g. wasm32 targets) can supply their own.
+pub(crate) fn encrypt_inner_with_rng<R: RngCore>(
+    rng: &mut R,
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+    override_random_nonce: Option<&Nonce>,
+) -> Result<String, Error> {
+    let nonce = match override_random_nonce {
+        Some(nonce) => nonce.to_owned(),
+        None => {
+            let mut bytes: [u8; 32] = [0; 32];
+            rng.fill_bytes(&mut bytes);
+            Nonce::new(bytes)
+        }
+    };
+    seal(Version::V2, conversation_key, &nonce, plaintext)
+}
+
+/// Derives the SIV key used by [`encrypt_deterministic`]: a second
+/// HKDF-expand output from the conversation key, kept separate from the
+/// per-nonce message keys so that the synthetic nonce it produces can't be
+/// confused with (or used to recover) the ChaCha20/HMAC keys for any
+/// message.
+fn derive_siv_key(conversation_key: &ConversationKey) -> Result<[u8; 32], Error> {
+    let hk: Hkdf<Sha256> = Hkdf::from_prk(conversation_key.as_bytes())
+        .map_err(|_| Error::HkdfLength(conversation_key.as_bytes().len()))?;
+    let mut siv_key = [0u8; 32];
+    hk.expand(b"nip44-v2-siv", &mut siv_key)
+        .map_err(|_| Error::HkdfLength(siv_key.len()))?;
+    Ok(siv_key)
+}
+
+/// Misuse-resistant (SIV-style) encryption: the nonce is
+/// `HMAC-SHA256(siv_key, plaintext)` instead of drawn from randomness, so a
+/// caller that repeats `plaintext` under `conversation_key` reuses the same
+/// nonce rather than risking a random collision. Identical inputs therefore
+/// produce identical ciphertext; distinct messages get distinct nonces
+/// automatically. Tagged with [`Version::V2Deterministic`] so `decrypt`
+/// stays backward compatible with randomized payloads.
+pub(crate) fn encrypt_deterministic(
+    conversation_key: &ConversationKey,
+    plaintext: &str,
+) -> Result<String, Error> {
+    let siv_key = derive_siv_key(conversation_key)?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(&siv_key)?;
+    mac.update(plaintext.as_bytes());
+    let nonce = Nonce::new(mac.finalize().into_bytes().into());
+    seal(Version::V2Deterministic, conversation_key, &nonce, plaintext)
+}
+
+/// Decrypt a payload whose leading version byte has already been read and
+/// confirmed to be [`Version::V2`].
+///
+/// `binary_ciphertext` may come straight from an untrusted sender (e.g. via
+/// [`crate::seal::open`]), so a too-short or otherwise malformed slice fails
+/// with [`Error::InvalidPadding`] rather than panicking.
+pub(crate) fn decrypt_inner(
+    conversation_key: &ConversationKey,
+    binary_ciphertext: &[u8],
+) -> Result<String, Error> {
+    let dlen = binary_ciphertext.len();
+    if dlen < 1 + 32 + 32 {
+        return Err(Error::InvalidPadding);
+    }
+    let nonce = Nonce::new(binary_ciphertext[1..33].try_into().unwrap());
+    let mut buffer = binary_ciphertext[33..dlen - 32].to_owned();
+    let mac = &binary_ciphertext[dlen - 32..dlen];
+    let keys = get_message_keys(conversation_key, &nonce)?;
+    let mut calculated_mac = Hmac::<Sha256>::new_from_slice(&keys.auth())?;
+    calculated_mac.update(nonce.as_bytes());
+    calculated_mac.update(&buffer);
+    let calculated_mac_bytes = calculated_mac.finalize().into_bytes();
+    if !constant_time_eq::constant_time_eq(mac, calculated_mac_bytes.as_slice()) {
+        return Err(Error::InvalidMac);
+    }
+    let mut cipher = ChaCha20::new(&keys.encryption().into(), &keys.nonce().into());
+    cipher.apply_keystream(&mut buffer);
+    if buffer.len() < 2 {
+        return Err(Error::InvalidPadding);
+    }
+    let unpadded_len = u16::from_be_bytes(buffer[0..2].try_into().unwrap()) as usize;
+    if buffer.len() < 2 + unpadded_len {
+        return Err(Error::InvalidPadding);
+    }
+    let unpadded = &buffer[2..2 + unpadded_len];
+    if unpadded.is_empty() {
+        return Err(Error::MessageIsEmpty);
+    }
+    if unpadded.len() != unpadded_len {
+        return Err(Error::InvalidPadding);
+    }
+    if buffer.len() != 2 + calc_padding(unpadded_len) {
+        return Err(Error::InvalidPadding);
+    }
+    Ok(String::from_utf8(unpadded.to_vec())?)
+}
@@ -0,0 +1,42 @@
+use crate::Error;
+
+/// NIP-44 payload format version.
+///
+/// The leading byte of every encrypted payload identifies which version
+/// produced it; [`crate::decrypt`] reads that byte and dispatches to the
+/// matching codec. A future NIP-44 revision means adding a variant here
+/// plus its own codec module, not reworking the existing ones.
+///
+/// [`Version::V2Deterministic`] isn't a new wire format, just [`Version::V2`]
+/// with a synthetic (message-derived) nonce instead of a random one; it gets
+/// its own byte so [`crate::decrypt`] can't be tricked into treating a
+/// deterministic ciphertext as randomized or vice versa.
+///
+/// Byte `3` is `gnip44`'s own invention, not assigned by the NIP-44 spec: no
+/// other NIP-44 implementation will recognize it, so [`crate::encrypt_deterministic`]
+/// output only round-trips through this crate's own [`crate::decrypt`]. This is
+/// intentional (see [`Version::from_byte`]), not an oversight -- don't widen
+/// it into a general "unknown version" bucket without updating `decrypt` too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V2,
+    V2Deterministic,
+}
+
+impl Version {
+    pub(crate) fn byte(self) -> u8 {
+        match self {
+            Version::V2 => 2,
+            Version::V2Deterministic => 3,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> Result<Version, Error> {
+        match byte {
+            2 => Ok(Version::V2),
+            3 => Ok(Version::V2Deterministic),
+            b if b > 3 => Err(Error::UnsupportedFutureVersion),
+            _ => Err(Error::UnknownVersion),
+        }
+    }
+}